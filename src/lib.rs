@@ -27,7 +27,16 @@
 //! ```
 
 use std::collections::HashMap;
-use std::io::BufRead;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use chrono::Utc;
+use flate2::bufread::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression as GzCompression;
+use uuid::Uuid;
+
+// gzip member magic bytes, used to auto-detect compressed WARCs
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 
 // trim a string in place (no (re)allocations)
 fn rtrim(s: &mut String) {
@@ -55,6 +64,11 @@ impl CaseString {
     pub fn to_string(self) -> String {
         self.into()
     }
+
+    /// Borrow the (lowercased) inner string
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
 }
 
 impl PartialEq<String> for CaseString {
@@ -123,7 +137,95 @@ pub struct WarcRecord {
 }
 
 impl WarcRecord {
+    /// Construct a new record, auto-populating the mandatory `WARC-Record-ID`, `WARC-Date`
+    /// and `Content-Length` header fields.
+    ///
+    /// `WARC-Record-ID` is generated as a fresh `urn:uuid:` (v4), `WARC-Date` is the current
+    /// time in RFC3339 format, and `Content-Length` is computed from `content`. Any other
+    /// mandatory fields (notably `WARC-Type`) are left to the caller to set in `header`, since
+    /// their value depends on what kind of record is being produced.
+    ///
+    /// This allows a read -> modify -> write round trip without having to manually keep these
+    /// derived fields in sync.
+    ///
+    /// ```rust
+    /// use rust_warc::WarcRecord;
+    ///
+    /// use std::collections::HashMap;
+    ///
+    /// let mut header = HashMap::new();
+    /// header.insert("WARC-Type".into(), "warcinfo".into());
+    ///
+    /// let record = WarcRecord::new(header, b"hello".to_vec());
+    ///
+    /// assert_eq!(record.header.get(&"Content-Length".into()), Some(&"5".to_string()));
+    /// assert!(record.header.contains_key(&"WARC-Record-ID".into()));
+    /// assert!(record.header.contains_key(&"WARC-Date".into()));
+    /// ```
+    pub fn new(mut header: HashMap<CaseString, String>, content: Vec<u8>) -> Self {
+        header.insert(
+            "WARC-Record-ID".into(),
+            format!("<urn:uuid:{}>", Uuid::new_v4()),
+        );
+        header.insert("WARC-Date".into(), Utc::now().to_rfc3339());
+        header.insert("Content-Length".into(), content.len().to_string());
+
+        WarcRecord {
+            version: String::from("WARC/1.1"),
+            header,
+            content,
+        }
+    }
+
     pub fn parse(mut read: impl BufRead) -> Result<Self, WarcError> {
+        let version = Self::parse_version(&mut read)?;
+
+        Self::parse_body(version, read)
+    }
+
+    // parses everything past the already-read `WARC/1.x` version line; split out so a
+    // ParseMode::Tolerant reader can resync to a version line by hand and resume parsing here
+    // without re-reading one
+    fn parse_body(version: String, mut read: impl BufRead) -> Result<Self, WarcError> {
+        let (header, content_len) = Self::parse_header(&mut read)?;
+
+        let mut content = vec![0; content_len as usize];
+        if let Err(io) = read.read_exact(&mut content) {
+            return Err(WarcError::IO(io));
+        }
+
+        consume_trailing_linefeed(&mut read)?;
+
+        let record = WarcRecord {
+            version,
+            header,
+            content,
+        };
+
+        Ok(record)
+    }
+
+    /// Like [WarcRecord::parse], but returns the content as a bounded [ContentReader] over
+    /// `read` rather than eagerly buffering it into a `Vec<u8>`.
+    ///
+    /// For archives with multi-hundred-MB payloads, `parse`'s `vec![0; content_len]` allocation
+    /// per record is a memory problem; this lets callers process such records in constant
+    /// memory instead. Drop [StreamingRecord::content] (or call [ContentReader::finish] on it)
+    /// to drain any unread content and consume the trailing `\r\n\r\n`, leaving `read` ready for
+    /// the next record.
+    pub fn parse_streaming<R: BufRead>(mut read: R) -> Result<StreamingRecord<R>, WarcError> {
+        let version = Self::parse_version(&mut read)?;
+        let (header, content_len) = Self::parse_header(&mut read)?;
+
+        Ok(StreamingRecord {
+            version,
+            header,
+            content: ContentReader::new(read, content_len),
+        })
+    }
+
+    // read and validate the `WARC/1.x` version line
+    fn parse_version(mut read: impl BufRead) -> Result<String, WarcError> {
         let mut version = String::new();
 
         if let Err(io) = read.read_line(&mut version) {
@@ -140,6 +242,38 @@ impl WarcRecord {
             return Err(WarcError::Malformed(String::from("Unknown WARC version")));
         }
 
+        Ok(version)
+    }
+
+    // read the header block up to (and consuming) the terminating blank line, and pull out the
+    // mandatory Content-Length
+    fn parse_header(
+        mut read: impl BufRead,
+    ) -> Result<(HashMap<CaseString, String>, u64), WarcError> {
+        let header = Self::parse_header_lines(&mut read)?;
+
+        let content_len = header.get(&"Content-Length".into());
+        if content_len.is_none() {
+            return Err(WarcError::Malformed(String::from(
+                "Content-Length is missing",
+            )));
+        }
+
+        let content_len = content_len.unwrap().parse::<u64>();
+        if content_len.is_err() {
+            return Err(WarcError::Malformed(String::from(
+                "Content-Length is not a number",
+            )));
+        }
+
+        Ok((header, content_len.unwrap()))
+    }
+
+    // read "Name: value" lines up to (and consuming) the terminating blank line; shared between
+    // the WARC header block and the embedded HTTP header block parsed by `http`
+    fn parse_header_lines(
+        mut read: impl BufRead,
+    ) -> Result<HashMap<CaseString, String>, WarcError> {
         let mut header = HashMap::<CaseString, String>::with_capacity(16); // no allocations if <= 16 header fields
 
         loop {
@@ -154,58 +288,185 @@ impl WarcRecord {
                 break;
             }
 
-            // todo field multiline continuations
-
             rtrim(&mut line_buf);
 
             if let Some(semi) = line_buf.find(':') {
-                let value = line_buf.split_off(semi + 1).trim().to_string();
+                let mut value = line_buf.split_off(semi + 1).trim().to_string();
                 line_buf.pop(); // eat colon
                 rtrim(&mut line_buf);
+                let name = line_buf;
+
+                // field continuation: a following line starting with a space or tab folds into
+                // this field's value, with its leading whitespace collapsed to a single space
+                loop {
+                    let is_continuation = read
+                        .fill_buf()
+                        .map_err(WarcError::IO)?
+                        .first()
+                        .is_some_and(|&b| b == b' ' || b == b'\t');
+
+                    if !is_continuation {
+                        break;
+                    }
 
-                header.insert(line_buf.into(), value);
+                    let mut continuation = String::new();
+                    if let Err(io) = read.read_line(&mut continuation) {
+                        return Err(WarcError::IO(io));
+                    }
+                    rtrim(&mut continuation);
+
+                    value.push(' ');
+                    value.push_str(continuation.trim());
+                }
+
+                header.insert(name.into(), value);
             } else {
                 return Err(WarcError::Malformed(String::from("Invalid header field")));
             }
         }
 
-        let content_len = header.get(&"Content-Length".into());
-        if content_len.is_none() {
-            return Err(WarcError::Malformed(String::from(
-                "Content-Length is missing",
-            )));
-        }
+        Ok(header)
+    }
 
-        let content_len = content_len.unwrap().parse::<usize>();
-        if content_len.is_err() {
-            return Err(WarcError::Malformed(String::from(
-                "Content-Length is not a number",
-            )));
+    /// Parse the HTTP message embedded in `self.content`, for `request`/`response` records
+    /// whose `Content-Type` is `application/http`.
+    ///
+    /// Returns `None` when the record isn't carrying an HTTP message (a different
+    /// `Content-Type`) at all; once we know one should be there, a parse failure is surfaced as
+    /// `Some(Err(..))` rather than silently swallowed.
+    ///
+    /// ```rust
+    /// use rust_warc::WarcRecord;
+    ///
+    /// let content = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nhello";
+    /// let mut data = format!(
+    ///     "WARC/1.1\r\nWARC-Type: response\r\nContent-Type: application/http\r\nContent-Length: {}\r\n\r\n",
+    ///     content.len()
+    /// ).into_bytes();
+    /// data.extend_from_slice(content);
+    /// data.extend_from_slice(b"\r\n\r\n");
+    ///
+    /// let record = WarcRecord::parse(&data[..]).unwrap();
+    ///
+    /// let http = record.http().unwrap().unwrap();
+    /// assert_eq!(http.start_line, "HTTP/1.1 200 OK");
+    /// assert_eq!(http.headers.get(&"Content-Type".into()), Some(&"text/plain".into()));
+    /// assert_eq!(http.body, b"hello");
+    /// ```
+    pub fn http(&self) -> Option<Result<HttpPayload<'_>, WarcError>> {
+        let content_type = self.header.get(&"Content-Type".into())?;
+        if !content_type.starts_with("application/http") {
+            return None;
         }
 
-        let content_len = content_len.unwrap();
-        let mut content = vec![0; content_len];
-        if let Err(io) = read.read_exact(&mut content) {
+        Some(self.parse_http())
+    }
+
+    fn parse_http(&self) -> Result<HttpPayload<'_>, WarcError> {
+        let mut read = &self.content[..];
+
+        let mut start_line = String::new();
+        if let Err(io) = read.read_line(&mut start_line) {
             return Err(WarcError::IO(io));
         }
+        rtrim(&mut start_line);
 
-        let mut linefeed = [0u8; 4];
-        if let Err(io) = read.read_exact(&mut linefeed) {
-            return Err(WarcError::IO(io));
+        let headers = Self::parse_header_lines(&mut read)?;
+
+        Ok(HttpPayload {
+            start_line,
+            headers,
+            body: read,
+        })
+    }
+}
+
+/// The HTTP message embedded in a `request`/`response` record's `application/http` content,
+/// returned by [WarcRecord::http]
+pub struct HttpPayload<'a> {
+    /// The HTTP status line (`HTTP/1.1 200 OK`) or request line (`GET / HTTP/1.1`)
+    pub start_line: String,
+    /// HTTP header fields
+    pub headers: HashMap<CaseString, String>,
+    /// The remainder of the content block after the HTTP header block
+    pub body: &'a [u8],
+}
+
+// consume the mandatory `\r\n\r\n` that terminates a record's content block
+fn consume_trailing_linefeed(mut read: impl Read) -> Result<(), WarcError> {
+    let mut linefeed = [0u8; 4];
+    if let Err(io) = read.read_exact(&mut linefeed) {
+        return Err(WarcError::IO(io));
+    }
+    if linefeed != [13, 10, 13, 10] {
+        return Err(WarcError::Malformed(String::from(
+            "No double linefeed after record content",
+        )));
+    }
+
+    Ok(())
+}
+
+/// The result of [WarcRecord::parse_streaming]: headers plus a bounded reader over the content
+/// block instead of an eagerly buffered `Vec<u8>`.
+pub struct StreamingRecord<R: Read> {
+    /// WARC version string (WARC/1.1)
+    pub version: String,
+    /// Record header fields
+    pub header: HashMap<CaseString, String>,
+    /// Bounded reader over the record's content block, limited to exactly `Content-Length`
+    /// bytes
+    pub content: ContentReader<R>,
+}
+
+/// A bounded [Read] over a record's content block, returned by [WarcRecord::parse_streaming]
+/// as [StreamingRecord::content].
+///
+/// Reading is capped at exactly `Content-Length` bytes. When [ContentReader::finish] is called
+/// (or this reader is dropped) any unread content is drained and the trailing `\r\n\r\n` is
+/// consumed from the underlying stream so the caller can go on to read the next record; reading
+/// the content to its end does not by itself consume the trailer.
+pub struct ContentReader<R: Read> {
+    take: std::io::Take<R>,
+    finished: bool,
+}
+
+impl<R: Read> ContentReader<R> {
+    fn new(read: R, content_len: u64) -> Self {
+        ContentReader {
+            take: read.take(content_len),
+            finished: false,
         }
-        if linefeed != [13, 10, 13, 10] {
-            return Err(WarcError::Malformed(String::from(
-                "No double linefeed after record content",
-            )));
+    }
+
+    /// Drain any unread content and consume the trailing `\r\n\r\n`.
+    ///
+    /// Called automatically on drop, but exposed so callers that want to observe an IO or
+    /// [WarcError::Malformed] error (a missing double linefeed) can do so explicitly instead of
+    /// having it silently swallowed by `Drop`.
+    pub fn finish(&mut self) -> Result<(), WarcError> {
+        if self.finished {
+            return Ok(());
         }
+        self.finished = true;
 
-        let record = WarcRecord {
-            version,
-            header,
-            content,
-        };
+        std::io::copy(&mut self.take, &mut std::io::sink()).map_err(WarcError::IO)?;
 
-        Ok(record)
+        consume_trailing_linefeed(self.take.get_mut())
+    }
+}
+
+impl<R: Read> Read for ContentReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.take.read(buf)
+    }
+}
+
+impl<R: Read> Drop for ContentReader<R> {
+    fn drop(&mut self) {
+        // best effort: Drop can't propagate errors, so a record whose trailing `\r\n\r\n` turns
+        // out to be malformed is simply left for the next read to fail on instead of panicking
+        let _ = self.finish();
     }
 }
 
@@ -215,6 +476,60 @@ pub enum WarcError {
     Malformed(String),
     IO(std::io::Error),
     EOF,
+    /// In [ParseMode::Tolerant], a malformed record was skipped while the reader scanned
+    /// forward to resynchronize on the next record (the next `WARC/1.` line, or the next gzip
+    /// member in [Compression::Gzip] mode); carries the number of bytes skipped. Unlike the other variants this is recoverable: the reader's next item is the
+    /// record the scan resynchronized onto, not `None`.
+    Resynced(u64),
+}
+
+/// How a [WarcReader] handles a malformed record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first malformed record and stop iterating, as [WarcReader] has always done
+    Strict,
+    /// On a malformed record, scan forward to the next `WARC/1.` line (the next gzip member in
+    /// [Compression::Gzip] mode) and resume from there instead of failing the rest of the archive
+    Tolerant,
+}
+
+/// On-disk compression of a WARC stream
+///
+/// Real-world `.warc.gz` files don't gzip the whole archive as one stream: each record is
+/// compressed as its own independent gzip member, concatenated back to back, so a reader can
+/// inflate a single record without having to decode everything before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Plain, uncompressed WARC/1.x text
+    Plain,
+    /// One gzip member per record
+    Gzip,
+}
+
+// wraps the underlying byte source, counting bytes consumed so records can report their
+// starting offset (needed for the CDX index and for WarcReader::read_at)
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.count += amt as u64;
+    }
 }
 
 /// WARC reader instance
@@ -235,18 +550,167 @@ pub enum WarcError {
 /// assert_eq!(warc.count(), 2);
 /// ```
 pub struct WarcReader<R> {
-    read: R,
+    read: CountingReader<R>,
+    compression: Compression,
+    parse_mode: ParseMode,
+    // a version line already consumed while resynchronizing in ParseMode::Tolerant, to be fed
+    // straight into WarcRecord::parse_body on the next iteration instead of being re-read
+    pending_version: Option<String>,
     valid_state: bool,
 }
 
 impl<R: BufRead> WarcReader<R> {
     /// Create a new WarcReader from a [BufRead] input
+    ///
+    /// The stream's compression is auto-detected by sniffing the first two bytes for the gzip
+    /// magic number; use [WarcReader::with_options] to force a specific [Compression] instead.
+    /// Defaults to [ParseMode::Strict]; see [WarcReader::parse_mode] to change that.
     pub fn new(read: R) -> Self {
+        Self::with_options(read, None)
+    }
+
+    /// Create a new WarcReader, optionally forcing a [Compression] rather than auto-detecting it
+    pub fn with_options(mut read: R, compression: Option<Compression>) -> Self {
+        let compression = compression.unwrap_or_else(|| {
+            let looks_gzip = read
+                .fill_buf()
+                .map(|buf| buf.starts_with(&GZIP_MAGIC))
+                .unwrap_or(false);
+
+            if looks_gzip {
+                Compression::Gzip
+            } else {
+                Compression::Plain
+            }
+        });
+
         Self {
-            read,
+            read: CountingReader {
+                inner: read,
+                count: 0,
+            },
+            compression,
+            parse_mode: ParseMode::Strict,
+            pending_version: None,
             valid_state: true,
         }
     }
+
+    /// Set the [ParseMode] used to handle malformed records
+    pub fn parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Iterate records alongside the starting byte offset and on-disk length (the compressed
+    /// member length in [Compression::Gzip] mode) they were read from, suitable for building a
+    /// CDX-style index; see [WarcReader::read_at] to later jump straight to a record using one
+    /// of these offsets.
+    pub fn with_offsets(self) -> OffsetIter<R> {
+        OffsetIter { reader: self }
+    }
+
+    // parse a single record, honoring `self.compression`; in Gzip mode each record is its own
+    // gzip member, so it is fully inflated into memory before being handed to WarcRecord::parse
+    fn parse_one(&mut self) -> Result<WarcRecord, WarcError> {
+        match self.compression {
+            Compression::Plain => {
+                let start_count = self.read.count;
+                let result = match self.pending_version.take() {
+                    Some(version) => WarcRecord::parse_body(version, &mut self.read),
+                    None => WarcRecord::parse(&mut self.read),
+                };
+
+                self.tolerate(result, |reader| reader.resync(start_count))
+            }
+            Compression::Gzip => {
+                // GzDecoder::read_to_end returns Err(UnexpectedEof) rather than Ok(0) once the
+                // underlying stream is exhausted, so check for end-of-stream ourselves before
+                // ever constructing a decoder
+                if self.read.fill_buf().map_err(WarcError::IO)?.is_empty() {
+                    return Err(WarcError::EOF);
+                }
+
+                let start_count = self.read.count;
+                let mut decoded = Vec::new();
+                match GzDecoder::new(&mut self.read).read_to_end(&mut decoded) {
+                    // the gzip layer already delineates one record per member, so a malformed
+                    // member has no "next record" to scan for: just report the whole member as
+                    // skipped and move on to the next member on the following iteration
+                    Ok(n) => self.tolerate(WarcRecord::parse(&mut &decoded[..]), |_| {
+                        Err(WarcError::Resynced(n as u64))
+                    }),
+                    // a corrupt/truncated gzip member is just as unrecoverable-in-place as a
+                    // malformed one: there's no "next record" within it to scan for. The failed
+                    // decoder may have consumed anywhere from zero bytes (e.g. the member's own
+                    // magic is corrupt) up to the whole member (e.g. only its trailing checksum
+                    // is corrupt) before erroring, so the stream position can't be trusted to sit
+                    // on the next member's boundary; scan forward for the next gzip magic by hand
+                    // instead of guessing, the same way Plain mode scans for the next "WARC/1."
+                    // line, to guarantee forward progress and land exactly on the next member
+                    Err(_) if self.parse_mode == ParseMode::Tolerant => {
+                        self.resync_gzip(start_count)
+                    }
+                    Err(io) => Err(WarcError::IO(io)),
+                }
+            }
+        }
+    }
+
+    // in ParseMode::Tolerant, turn a Malformed error into a resync attempt; otherwise pass the
+    // result through unchanged
+    fn tolerate(
+        &mut self,
+        result: Result<WarcRecord, WarcError>,
+        on_malformed: impl FnOnce(&mut Self) -> Result<WarcRecord, WarcError>,
+    ) -> Result<WarcRecord, WarcError> {
+        match result {
+            Err(WarcError::Malformed(_)) if self.parse_mode == ParseMode::Tolerant => {
+                on_malformed(self)
+            }
+            other => other,
+        }
+    }
+
+    // scan forward to the next line starting with "WARC/1." and stash it as `pending_version`
+    // for the next iteration to resume from, reporting the number of bytes skipped since
+    // `start_count` (the start of the malformed record) up to that line
+    fn resync(&mut self, start_count: u64) -> Result<WarcRecord, WarcError> {
+        loop {
+            let line_start = self.read.count;
+            let mut line = String::new();
+
+            match self.read.read_line(&mut line) {
+                Ok(0) => return Err(WarcError::EOF),
+                Ok(_) => {
+                    if line.starts_with("WARC/1.") {
+                        rtrim(&mut line);
+                        self.pending_version = Some(line);
+                        return Err(WarcError::Resynced(line_start - start_count));
+                    }
+                }
+                Err(io) => return Err(WarcError::IO(io)),
+            }
+        }
+    }
+
+    // scan forward byte by byte until the next gzip member's magic bytes come into view,
+    // reporting the total number of bytes skipped since `start_count` (which may already
+    // include bytes a failed GzDecoder consumed trying to parse the corrupt member)
+    fn resync_gzip(&mut self, start_count: u64) -> Result<WarcRecord, WarcError> {
+        loop {
+            let buf = self.read.fill_buf().map_err(WarcError::IO)?;
+
+            if buf.is_empty() {
+                return Err(WarcError::EOF);
+            }
+            if buf.starts_with(&GZIP_MAGIC) {
+                return Err(WarcError::Resynced(self.read.count - start_count));
+            }
+
+            self.read.consume(1);
+        }
+    }
 }
 
 impl<R: BufRead> Iterator for WarcReader<R> {
@@ -257,9 +721,11 @@ impl<R: BufRead> Iterator for WarcReader<R> {
             return None;
         }
 
-        match WarcRecord::parse(&mut self.read) {
+        match self.parse_one() {
             Ok(item) => Some(Ok(item)),
             Err(WarcError::EOF) => None,
+            // recoverable: the next call to `next` continues from where the scan left off
+            Err(e @ WarcError::Resynced(_)) => Some(Err(e)),
             Err(e) => {
                 self.valid_state = false;
                 Some(Err(e))
@@ -268,6 +734,245 @@ impl<R: BufRead> Iterator for WarcReader<R> {
     }
 }
 
+/// Iterator returned by [WarcReader::with_offsets]
+pub struct OffsetIter<R> {
+    reader: WarcReader<R>,
+}
+
+impl<R: BufRead> Iterator for OffsetIter<R> {
+    type Item = Result<(u64, u64, WarcRecord), WarcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.reader.valid_state {
+            return None;
+        }
+
+        let offset = self.reader.read.count;
+
+        match self.reader.parse_one() {
+            Ok(record) => {
+                let length = self.reader.read.count - offset;
+                Some(Ok((offset, length, record)))
+            }
+            Err(WarcError::EOF) => None,
+            Err(e @ WarcError::Resynced(_)) => Some(Err(e)),
+            Err(e) => {
+                self.reader.valid_state = false;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> WarcReader<R> {
+    /// Seek to a byte offset (as produced by [WarcReader::with_offsets] or a CDX index) and
+    /// parse exactly one record there: one gzip member in [Compression::Gzip] mode, or one
+    /// plain record otherwise. This allows O(1) lookups against a prebuilt index instead of
+    /// scanning the whole archive.
+    pub fn read_at(mut reader: R, offset: u64) -> Result<WarcRecord, WarcError> {
+        reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(WarcError::IO)?;
+
+        let mut reader = BufReader::new(reader);
+        let gzip = reader
+            .fill_buf()
+            .map(|buf| buf.starts_with(&GZIP_MAGIC))
+            .unwrap_or(false);
+
+        if gzip {
+            let mut decoded = Vec::new();
+            GzDecoder::new(reader)
+                .read_to_end(&mut decoded)
+                .map_err(WarcError::IO)?;
+            WarcRecord::parse(&mut &decoded[..])
+        } else {
+            WarcRecord::parse(&mut reader)
+        }
+    }
+}
+
+/// A single entry of a CDX-style offset index: a record's position in the archive plus the
+/// handful of header fields most commonly used to filter an archive without re-parsing it.
+#[derive(Debug, Clone)]
+pub struct CdxEntry {
+    pub offset: u64,
+    pub length: u64,
+    pub warc_type: Option<String>,
+    pub target_uri: Option<String>,
+    pub date: Option<String>,
+    pub content_type: Option<String>,
+    pub digest: Option<String>,
+}
+
+impl CdxEntry {
+    /// Build a CDX entry from a record yielded by [WarcReader::with_offsets]
+    pub fn from_record(offset: u64, length: u64, record: &WarcRecord) -> Self {
+        let field = |name: &str| record.header.get(&name.into()).cloned();
+
+        CdxEntry {
+            offset,
+            length,
+            warc_type: field("WARC-Type"),
+            target_uri: field("WARC-Target-URI"),
+            date: field("WARC-Date"),
+            content_type: field("Content-Type"),
+            digest: field("WARC-Payload-Digest").or_else(|| field("WARC-Block-Digest")),
+        }
+    }
+}
+
+// field order used by write_cdx's header line
+const CDX_FIELDS: &str = "offset length warc-type target-uri date content-type digest";
+
+/// Write a sequence of [CdxEntry] out as CDX lines: a header line naming the (space-separated)
+/// field order, followed by one line per entry with `-` standing in for an absent field.
+pub fn write_cdx(entries: &[CdxEntry], mut write: impl Write) -> std::io::Result<()> {
+    writeln!(write, " CDX {}", CDX_FIELDS)?;
+
+    for entry in entries {
+        writeln!(
+            write,
+            "{} {} {} {} {} {} {}",
+            entry.offset,
+            entry.length,
+            entry.warc_type.as_deref().unwrap_or("-"),
+            entry.target_uri.as_deref().unwrap_or("-"),
+            entry.date.as_deref().unwrap_or("-"),
+            entry.content_type.as_deref().unwrap_or("-"),
+            entry.digest.as_deref().unwrap_or("-"),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// WARC field names in the capitalization most tools/crawlers expect to see on the wire.
+///
+/// [CaseString] lowercases everything for comparison purposes, so this table is needed to
+/// recover a sensible capitalization when serializing a header back out. Fields not listed here
+/// fall back to title-casing each hyphen-separated segment.
+const KNOWN_FIELDS: &[&str] = &[
+    "WARC-Type",
+    "WARC-Record-ID",
+    "WARC-Date",
+    "WARC-Concurrent-To",
+    "WARC-Block-Digest",
+    "WARC-Payload-Digest",
+    "WARC-IP-Address",
+    "WARC-Refers-To",
+    "WARC-Target-URI",
+    "WARC-Truncated",
+    "WARC-Warcinfo-ID",
+    "WARC-Filename",
+    "WARC-Profile",
+    "WARC-Identified-Payload-Type",
+    "WARC-Segment-Number",
+    "WARC-Segment-Origin-ID",
+    "WARC-Segment-Total-Length",
+    "Content-Type",
+    "Content-Length",
+];
+
+// recover a sensible capitalization for a lowercased header field name
+fn canonical_field_name(name: &str) -> String {
+    for known in KNOWN_FIELDS {
+        if known.eq_ignore_ascii_case(name) {
+            return (*known).to_string();
+        }
+    }
+
+    name.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// WARC writer instance
+///
+/// Serializes [WarcRecord]s back to a valid WARC stream, the counterpart to [WarcReader].
+///
+/// # Usage
+/// ```rust
+/// use rust_warc::{WarcRecord, WarcWriter};
+///
+/// use std::collections::HashMap;
+///
+/// let mut header = HashMap::new();
+/// header.insert("WARC-Type".into(), "warcinfo".into());
+/// let record = WarcRecord::new(header, b"hello".to_vec());
+///
+/// let mut buf = Vec::new();
+/// let mut writer = WarcWriter::new(&mut buf);
+/// writer.write(&record).unwrap();
+///
+/// assert!(buf.starts_with(b"WARC/1.1\r\n"));
+/// assert!(buf.ends_with(b"\r\n\r\n"));
+/// ```
+pub struct WarcWriter<W> {
+    write: W,
+    compression: Compression,
+}
+
+impl<W: Write> WarcWriter<W> {
+    /// Create a new, uncompressed WarcWriter around a [Write] sink
+    pub fn new(write: W) -> Self {
+        Self::with_options(write, Compression::Plain)
+    }
+
+    /// Create a new WarcWriter, writing each record as its own gzip member when `compression`
+    /// is [Compression::Gzip]
+    pub fn with_options(write: W, compression: Compression) -> Self {
+        Self { write, compression }
+    }
+
+    /// Serialize a single record: the version line, each header field, the terminating blank
+    /// line, the content block and the mandatory trailing `\r\n\r\n`.
+    ///
+    /// In [Compression::Gzip] mode the record is flushed as its own gzip member, so that a
+    /// reader can inflate and seek to individual records without decoding the whole archive.
+    pub fn write(&mut self, record: &WarcRecord) -> Result<(), WarcError> {
+        match self.compression {
+            Compression::Plain => Self::write_record(&mut self.write, record),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(&mut self.write, GzCompression::default());
+                Self::write_record(&mut encoder, record)?;
+                encoder.finish().map_err(WarcError::IO)?;
+
+                Ok(())
+            }
+        }
+    }
+
+    fn write_record(write: &mut impl Write, record: &WarcRecord) -> Result<(), WarcError> {
+        write
+            .write_all(record.version.as_bytes())
+            .map_err(WarcError::IO)?;
+        write.write_all(b"\r\n").map_err(WarcError::IO)?;
+
+        for (name, value) in record.header.iter() {
+            write
+                .write_all(canonical_field_name(name.as_str()).as_bytes())
+                .map_err(WarcError::IO)?;
+            write.write_all(b": ").map_err(WarcError::IO)?;
+            write.write_all(value.as_bytes()).map_err(WarcError::IO)?;
+            write.write_all(b"\r\n").map_err(WarcError::IO)?;
+        }
+
+        write.write_all(b"\r\n").map_err(WarcError::IO)?;
+        write.write_all(&record.content).map_err(WarcError::IO)?;
+        write.write_all(b"\r\n\r\n").map_err(WarcError::IO)?;
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -304,4 +1009,282 @@ mod tests {
         let item = item.unwrap();
         assert!(item.is_err()); // incomplete record
     }
+
+    #[test]
+    fn write_then_read_round_trips() {
+        let mut header = HashMap::new();
+        header.insert("WARC-Type".into(), "warcinfo".into());
+        let record = WarcRecord::new(header, b"hello".to_vec());
+
+        let mut buf = Vec::new();
+        WarcWriter::new(&mut buf).write(&record).unwrap();
+
+        let mut warc = WarcReader::new(&buf[..]);
+        let read_back = warc.next().unwrap().unwrap();
+
+        assert_eq!(read_back.version, "WARC/1.1");
+        assert_eq!(
+            read_back.header.get(&"WARC-Type".into()),
+            Some(&"warcinfo".into())
+        );
+        assert_eq!(
+            read_back.header.get(&"Content-Length".into()),
+            Some(&"5".to_string())
+        );
+        assert_eq!(read_back.content, b"hello");
+        assert!(warc.next().is_none());
+    }
+
+    #[test]
+    fn gzip_per_record_round_trips_and_ends_cleanly() {
+        let mut buf = Vec::new();
+        let mut writer = WarcWriter::with_options(&mut buf, Compression::Gzip);
+        for i in 0..3 {
+            let mut header = HashMap::new();
+            header.insert("WARC-Type".into(), "resource".into());
+            writer
+                .write(&WarcRecord::new(header, format!("record {i}").into_bytes()))
+                .unwrap();
+        }
+
+        // each record is its own gzip member, concatenated back to back
+        assert!(buf.starts_with(&GZIP_MAGIC));
+
+        let warc = WarcReader::new(&buf[..]);
+        let records: Vec<_> = warc.collect();
+
+        assert_eq!(records.len(), 3);
+        for (i, item) in records.into_iter().enumerate() {
+            assert_eq!(item.unwrap().content, format!("record {i}").into_bytes());
+        }
+    }
+
+    #[test]
+    fn offsets_feed_cdx_and_read_at() {
+        let mut buf = Vec::new();
+        let mut writer = WarcWriter::new(&mut buf);
+        for i in 0..3 {
+            let mut header = HashMap::new();
+            header.insert("WARC-Type".into(), "resource".into());
+            header.insert("WARC-Target-URI".into(), format!("https://example.com/{i}"));
+            writer
+                .write(&WarcRecord::new(header, format!("record {i}").into_bytes()))
+                .unwrap();
+        }
+
+        let warc = WarcReader::new(&buf[..]);
+        let entries: Vec<CdxEntry> = warc
+            .with_offsets()
+            .map(|item| {
+                let (offset, length, record) = item.unwrap();
+                CdxEntry::from_record(offset, length, &record)
+            })
+            .collect();
+        assert_eq!(entries.len(), 3);
+
+        let mut cdx = Vec::new();
+        write_cdx(&entries, &mut cdx).unwrap();
+        let cdx = String::from_utf8(cdx).unwrap();
+        assert!(cdx.starts_with(" CDX offset length warc-type target-uri date content-type digest\n"));
+        assert!(cdx.contains("https://example.com/1"));
+
+        // the recorded offset lets us jump straight to a record without reparsing the rest
+        let third = &entries[2];
+        let record = WarcReader::read_at(std::io::Cursor::new(buf), third.offset).unwrap();
+        assert_eq!(record.content, b"record 2");
+    }
+
+    fn resource_record(uri: &str) -> WarcRecord {
+        let mut header = HashMap::new();
+        header.insert("WARC-Type".into(), "resource".into());
+        header.insert("WARC-Target-URI".into(), uri.into());
+        WarcRecord::new(header, b"ok".to_vec())
+    }
+
+    #[test]
+    fn tolerant_mode_resyncs_past_a_malformed_record() {
+        let mut buf = Vec::new();
+        WarcWriter::new(&mut buf)
+            .write(&resource_record("https://example.com/a"))
+            .unwrap();
+        // no Content-Length: WarcRecord::parse will report this record as Malformed
+        buf.extend_from_slice(b"WARC/1.1\r\nWARC-Type: resource\r\n\r\n");
+        WarcWriter::new(&mut buf)
+            .write(&resource_record("https://example.com/b"))
+            .unwrap();
+
+        let mut warc = WarcReader::new(&buf[..]).parse_mode(ParseMode::Tolerant);
+
+        let first = warc.next().unwrap().unwrap();
+        assert_eq!(
+            first.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/a".into())
+        );
+
+        assert!(matches!(warc.next(), Some(Err(WarcError::Resynced(_)))));
+
+        let third = warc.next().unwrap().unwrap();
+        assert_eq!(
+            third.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/b".into())
+        );
+        assert!(warc.next().is_none());
+    }
+
+    #[test]
+    fn tolerant_mode_skips_a_corrupt_gzip_member() {
+        let mut buf = Vec::new();
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/a"))
+            .unwrap();
+
+        // corrupt the second record's gzip member in place (flip the last trailer byte) so the
+        // member decodes but fails its checksum, without shifting where the next member starts
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/b"))
+            .unwrap();
+        *buf.last_mut().unwrap() ^= 0xff;
+
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/c"))
+            .unwrap();
+
+        let mut warc = WarcReader::new(&buf[..]).parse_mode(ParseMode::Tolerant);
+
+        let first = warc.next().unwrap().unwrap();
+        assert_eq!(
+            first.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/a".into())
+        );
+
+        assert!(matches!(warc.next(), Some(Err(WarcError::Resynced(_)))));
+
+        let third = warc.next().unwrap().unwrap();
+        assert_eq!(
+            third.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/c".into())
+        );
+        assert!(warc.next().is_none());
+    }
+
+    #[test]
+    fn tolerant_mode_scans_past_a_member_with_corrupt_magic_bytes() {
+        let mut buf = Vec::new();
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/a"))
+            .unwrap();
+
+        // corrupt the second record's gzip member at its very first bytes (its magic number),
+        // so GzDecoder fails immediately having consumed nothing -- the decoded.len()/consumed
+        // count alone can't tell us how far to skip here, since no progress was made at all
+        let second_start = buf.len();
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/b"))
+            .unwrap();
+        buf[second_start] ^= 0xff;
+        buf[second_start + 1] ^= 0xff;
+
+        WarcWriter::with_options(&mut buf, Compression::Gzip)
+            .write(&resource_record("https://example.com/c"))
+            .unwrap();
+
+        let mut warc = WarcReader::new(&buf[..]).parse_mode(ParseMode::Tolerant);
+
+        let first = warc.next().unwrap().unwrap();
+        assert_eq!(
+            first.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/a".into())
+        );
+
+        // the corrupt member may take more than one Resynced step to crawl past (each decode
+        // attempt only consumes as far as it gets before failing), but it must make forward
+        // progress and terminate rather than looping on the same Resynced(0) forever
+        let mut resyncs = 0;
+        let third = loop {
+            match warc.next() {
+                Some(Ok(record)) => break record,
+                Some(Err(WarcError::Resynced(_))) => {
+                    resyncs += 1;
+                    assert!(resyncs < 50, "resync made no forward progress");
+                }
+                Some(Err(_)) => panic!("unexpected hard error"),
+                None => panic!("reached EOF before the third record"),
+            }
+        };
+        assert_eq!(
+            third.header.get(&"WARC-Target-URI".into()),
+            Some(&"https://example.com/c".into())
+        );
+        assert!(warc.next().is_none());
+    }
+
+    #[test]
+    fn parse_streaming_early_drop_resyncs_to_the_next_record() {
+        let mut buf = Vec::new();
+        let mut writer = WarcWriter::new(&mut buf);
+        writer
+            .write(&WarcRecord::new(
+                HashMap::new(),
+                b"hello world".to_vec(),
+            ))
+            .unwrap();
+        writer
+            .write(&WarcRecord::new(HashMap::new(), b"second".to_vec()))
+            .unwrap();
+
+        let mut reader = BufReader::new(&buf[..]);
+
+        {
+            let mut streaming = WarcRecord::parse_streaming(&mut reader).unwrap();
+            let mut partial = [0u8; 5];
+            streaming.content.read_exact(&mut partial).unwrap();
+            assert_eq!(&partial, b"hello");
+            // streaming.content is dropped here without reading "world", but Drop still has to
+            // drain it and consume the trailing \r\n\r\n for the next parse to line up
+        }
+
+        let mut second = WarcRecord::parse_streaming(&mut reader).unwrap();
+        let mut content = Vec::new();
+        second.content.read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"second");
+
+        // reading to the end does not consume the trailing \r\n\r\n; finish() does, and surfaces
+        // any error that Drop would otherwise swallow
+        second.content.finish().unwrap();
+    }
+
+    #[test]
+    fn http_parses_an_embedded_request_and_is_none_without_one() {
+        let mut header = HashMap::new();
+        header.insert("WARC-Type".into(), "request".into());
+        header.insert("Content-Type".into(), "application/http; msgtype=request".into());
+        let content = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n".to_vec();
+        let record = WarcRecord::new(header, content);
+
+        let http = record.http().unwrap().unwrap();
+        assert_eq!(http.start_line, "GET /index.html HTTP/1.1");
+        assert_eq!(
+            http.headers.get(&"Host".into()),
+            Some(&"example.com".into())
+        );
+        assert_eq!(http.body, b"");
+
+        let mut plain_header = HashMap::new();
+        plain_header.insert("WARC-Type".into(), "resource".into());
+        plain_header.insert("Content-Type".into(), "text/plain".into());
+        let plain = WarcRecord::new(plain_header, b"just text".to_vec());
+        assert!(plain.http().is_none());
+    }
+
+    #[test]
+    fn header_folds_multiple_consecutive_continuation_lines() {
+        let data = b"WARC/1.1\r\nWARC-Type: resource\r\nX-Long-Header: first\r\n\tsecond\r\n third\r\nContent-Length: 4\r\n\r\ntest\r\n\r\n";
+
+        let record = WarcRecord::parse(&data[..]).unwrap();
+
+        assert_eq!(
+            record.header.get(&"X-Long-Header".into()),
+            Some(&"first second third".to_string())
+        );
+    }
 }